@@ -15,15 +15,19 @@ extern crate futures;
 extern crate tokio_core;
 extern crate mio;
 extern crate mio_uds;
+extern crate libc;
 #[macro_use]
 extern crate log;
 
+use std::error;
 use std::fmt;
 use std::io::{self, Read, Write};
 use std::net::Shutdown;
+use std::os::unix::net;
 use std::os::unix::net::SocketAddr;
 use std::os::unix::prelude::*;
 use std::path::Path;
+use std::sync::Arc;
 
 use futures::{Future, Poll, Async};
 use futures::stream::Stream;
@@ -54,6 +58,32 @@ impl UnixListener {
         Ok(UnixListener { io: io })
     }
 
+    /// Consumes a `std::os::unix::net::UnixListener` and returns a
+    /// `UnixListener` bound to the given event loop's handle.
+    ///
+    /// This is useful for adopting a listening socket handed down by a
+    /// supervising process, e.g. via systemd socket activation.
+    pub fn from_std(listener: net::UnixListener, handle: &Handle) -> io::Result<UnixListener> {
+        let listener = try!(mio_uds::UnixListener::from_listener(listener));
+        UnixListener::new(listener, handle)
+    }
+
+    /// Wraps a raw file descriptor for a Unix listening socket and
+    /// registers it with the given event loop's handle.
+    ///
+    /// `std::os::unix::io::FromRawFd::from_raw_fd` cannot be implemented for
+    /// `UnixListener`, since adopting the descriptor requires the `Handle`
+    /// every socket in this crate is registered against; this associated
+    /// function plays the same role with that handle threaded through.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must represent a valid, open Unix listening socket that is not
+    /// owned elsewhere.
+    pub unsafe fn from_raw_fd(fd: RawFd, handle: &Handle) -> io::Result<UnixListener> {
+        UnixListener::from_std(net::UnixListener::from_raw_fd(fd), handle)
+    }
+
     /// Returns the local socket address of this listener.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.io.get_ref().local_addr()
@@ -171,6 +201,32 @@ impl UnixStream {
         Ok(UnixStream { io: io })
     }
 
+    /// Consumes a `std::os::unix::net::UnixStream` and returns a
+    /// `UnixStream` associated with the given event loop's handle.
+    ///
+    /// This is useful for adopting a connection handed down by a
+    /// supervising process, e.g. via systemd socket activation.
+    pub fn from_std(stream: net::UnixStream, handle: &Handle) -> io::Result<UnixStream> {
+        let stream = try!(mio_uds::UnixStream::from_stream(stream));
+        UnixStream::new(stream, handle)
+    }
+
+    /// Wraps a raw file descriptor for a connected Unix socket and
+    /// registers it with the given event loop's handle.
+    ///
+    /// `std::os::unix::io::FromRawFd::from_raw_fd` cannot be implemented for
+    /// `UnixStream`, since adopting the descriptor requires the `Handle`
+    /// every socket in this crate is registered against; this associated
+    /// function plays the same role with that handle threaded through.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must represent a valid, open, connected Unix socket (e.g. one
+    /// end of a `libc::socketpair`) that is not owned elsewhere.
+    pub unsafe fn from_raw_fd(fd: RawFd, handle: &Handle) -> io::Result<UnixStream> {
+        UnixStream::from_std(net::UnixStream::from_raw_fd(fd), handle)
+    }
+
     /// Test whether this socket is ready to be read or not.
     pub fn poll_read(&self) -> Async<()> {
         self.io.poll_read()
@@ -204,6 +260,63 @@ impl UnixStream {
     pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
         self.io.get_ref().shutdown(how)
     }
+
+    /// Returns the effective credentials of the process which called `connect`
+    /// or `pair`.
+    pub fn peer_cred(&self) -> io::Result<UCred> {
+        peer_cred(self)
+    }
+
+    /// Receives data on the socket from the remote address to which it is
+    /// connected, without removing that data from the queue. On success,
+    /// returns the number of bytes peeked.
+    ///
+    /// Successive calls return the same data, since `MSG_PEEK` is passed to
+    /// the underlying `recv` system call. This is useful for protocol
+    /// dispatchers that need to sniff a handshake prefix before handing the
+    /// stream off to the appropriate codec.
+    ///
+    /// This follows the same readiness pattern as the `Read` implementation:
+    /// it returns `Async::NotReady` (and arranges for the current task to be
+    /// notified) rather than an error when no data is currently available.
+    pub fn poll_peek(&self, buf: &mut [u8]) -> Poll<usize, io::Error> {
+        if self.io.poll_read().is_not_ready() {
+            return Ok(Async::NotReady)
+        }
+
+        let r = unsafe {
+            let fd = self.as_raw_fd();
+            libc::recv(fd,
+                       buf.as_mut_ptr() as *mut libc::c_void,
+                       buf.len(),
+                       libc::MSG_PEEK)
+        };
+
+        if r == -1 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::WouldBlock {
+                self.io.need_read();
+                return Ok(Async::NotReady)
+            }
+            return Err(e)
+        }
+
+        Ok(Async::Ready(r as usize))
+    }
+
+    /// Receives data on the socket without removing it from the queue.
+    ///
+    /// This is a convenience wrapper around `poll_peek` that returns an
+    /// `io::Result`: it returns a `WouldBlock` error rather than
+    /// `Async::NotReady`. Like `poll_peek`, it consults the reactor's
+    /// readiness state and registers the current task, so it must be called
+    /// from within a task.
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match try!(self.poll_peek(buf)) {
+            Async::Ready(n) => Ok(n),
+            Async::NotReady => Err(mio::would_block()),
+        }
+    }
 }
 
 impl Read for UnixStream {
@@ -269,6 +382,239 @@ impl AsRawFd for UnixStream {
     }
 }
 
+impl UnixStream {
+    /// Splits a `UnixStream` into a read half and a write half, which can be
+    /// used to read and write the stream concurrently.
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        let stream: &UnixStream = self;
+        (ReadHalf { stream: stream }, WriteHalf { stream: stream })
+    }
+
+    /// Splits a `UnixStream` into an owned read half and an owned write
+    /// half, each of which is independently `Send + 'static`.
+    ///
+    /// Unlike `split`, the owned halves can be moved into separately spawned
+    /// futures. The original stream can be recovered via
+    /// `OwnedWriteHalf::reunite`.
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        let inner = Arc::new(self);
+        (OwnedReadHalf { inner: inner.clone() }, OwnedWriteHalf { inner: inner })
+    }
+}
+
+/// The readable half of a `UnixStream`, created by `UnixStream::split`.
+#[derive(Debug)]
+pub struct ReadHalf<'a> {
+    stream: &'a UnixStream,
+}
+
+/// The writable half of a `UnixStream`, created by `UnixStream::split`.
+#[derive(Debug)]
+pub struct WriteHalf<'a> {
+    stream: &'a UnixStream,
+}
+
+impl<'a> ReadHalf<'a> {
+    /// Test whether this half is ready to be read from or not.
+    pub fn poll_read(&self) -> Async<()> {
+        self.stream.poll_read()
+    }
+}
+
+impl<'a> Read for ReadHalf<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl<'a> WriteHalf<'a> {
+    /// Test whether this half is ready to be written to or not.
+    pub fn poll_write(&self) -> Async<()> {
+        self.stream.poll_write()
+    }
+}
+
+impl<'a> Write for WriteHalf<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// The readable half of a `UnixStream`, created by `UnixStream::into_split`.
+///
+/// Unlike `ReadHalf`, this half owns its share of the stream (via an `Arc`)
+/// and so is `Send + 'static`, making it usable from an independently
+/// spawned future.
+#[derive(Debug)]
+pub struct OwnedReadHalf {
+    inner: Arc<UnixStream>,
+}
+
+/// The writable half of a `UnixStream`, created by `UnixStream::into_split`.
+///
+/// Unlike `WriteHalf`, this half owns its share of the stream (via an `Arc`)
+/// and so is `Send + 'static`, making it usable from an independently
+/// spawned future.
+#[derive(Debug)]
+pub struct OwnedWriteHalf {
+    inner: Arc<UnixStream>,
+}
+
+/// Error indicating that two halves were not from the same socket, returned
+/// by `OwnedWriteHalf::reunite`.
+#[derive(Debug)]
+pub struct ReuniteError(pub OwnedReadHalf, pub OwnedWriteHalf);
+
+impl fmt::Display for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tried to reunite halves that are not from the same socket")
+    }
+}
+
+impl error::Error for ReuniteError {
+    fn description(&self) -> &str {
+        "tried to reunite halves that are not from the same socket"
+    }
+}
+
+fn reunite(read: OwnedReadHalf, write: OwnedWriteHalf) -> Result<UnixStream, ReuniteError> {
+    if Arc::ptr_eq(&read.inner, &write.inner) {
+        drop(write);
+        Ok(Arc::try_unwrap(read.inner)
+            .expect("`UnixStream` is shared between more than just its two halves"))
+    } else {
+        Err(ReuniteError(read, write))
+    }
+}
+
+impl OwnedReadHalf {
+    /// Test whether this half is ready to be read from or not.
+    pub fn poll_read(&self) -> Async<()> {
+        self.inner.poll_read()
+    }
+}
+
+impl Read for OwnedReadHalf {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self.inner).read(buf)
+    }
+}
+
+impl OwnedWriteHalf {
+    /// Test whether this half is ready to be written to or not.
+    pub fn poll_write(&self) -> Async<()> {
+        self.inner.poll_write()
+    }
+
+    /// Attempts to join the two halves of a `UnixStream` back into a single
+    /// stream.
+    ///
+    /// Succeeds only if the two halves originated from the same call to
+    /// `UnixStream::into_split`.
+    pub fn reunite(self, other: OwnedReadHalf) -> Result<UnixStream, ReuniteError> {
+        reunite(other, self)
+    }
+}
+
+impl Write for OwnedWriteHalf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self.inner).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self.inner).flush()
+    }
+}
+
+/// Credentials of the process at the other end of a Unix socket, as reported
+/// by the kernel.
+///
+/// Constructed via `UnixStream::peer_cred`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UCred {
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    pid: Option<libc::pid_t>,
+}
+
+impl UCred {
+    /// Gets the PID of the process on the other side of the socket.
+    ///
+    /// This is only implemented on Linux. On other platforms this always
+    /// returns `None`.
+    pub fn pid(&self) -> Option<libc::pid_t> {
+        self.pid
+    }
+
+    /// Gets the UID of the process on the other side of the socket.
+    pub fn uid(&self) -> libc::uid_t {
+        self.uid
+    }
+
+    /// Gets the GID of the process on the other side of the socket.
+    pub fn gid(&self) -> libc::gid_t {
+        self.gid
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_cred(stream: &UnixStream) -> io::Result<UCred> {
+    use std::mem;
+
+    unsafe {
+        let raw_fd = stream.as_raw_fd();
+
+        let mut ucred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let ucred_size = mem::size_of::<libc::ucred>();
+        let mut ucred_size = ucred_size as libc::socklen_t;
+
+        let ret = libc::getsockopt(
+            raw_fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut ucred as *mut libc::ucred as *mut libc::c_void,
+            &mut ucred_size,
+        );
+
+        if ret == 0 && ucred_size as usize == mem::size_of::<libc::ucred>() {
+            Ok(UCred {
+                uid: ucred.uid,
+                gid: ucred.gid,
+                pid: Some(ucred.pid),
+            })
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          target_os = "openbsd",
+          target_os = "netbsd"))]
+fn peer_cred(stream: &UnixStream) -> io::Result<UCred> {
+    unsafe {
+        let raw_fd = stream.as_raw_fd();
+
+        let mut uid = 0;
+        let mut gid = 0;
+
+        let ret = libc::getpeereid(raw_fd, &mut uid, &mut gid);
+
+        if ret == 0 {
+            Ok(UCred { uid: uid, gid: gid, pid: None })
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
 /// An I/O object representing a Unix datagram socket.
 pub struct UnixDatagram {
     io: PollEvented<mio_uds::UnixDatagram>,
@@ -306,6 +652,32 @@ impl UnixDatagram {
         Ok(UnixDatagram { io: io })
     }
 
+    /// Consumes a `std::os::unix::net::UnixDatagram` and returns a
+    /// `UnixDatagram` associated with the given event loop's handle.
+    ///
+    /// This is useful for adopting a socket handed down by a supervising
+    /// process, e.g. via systemd socket activation.
+    pub fn from_std(socket: net::UnixDatagram, handle: &Handle) -> io::Result<UnixDatagram> {
+        let socket = try!(mio_uds::UnixDatagram::from_datagram(socket));
+        UnixDatagram::new(socket, handle)
+    }
+
+    /// Wraps a raw file descriptor for a Unix datagram socket and registers
+    /// it with the given event loop's handle.
+    ///
+    /// `std::os::unix::io::FromRawFd::from_raw_fd` cannot be implemented for
+    /// `UnixDatagram`, since adopting the descriptor requires the `Handle`
+    /// every socket in this crate is registered against; this associated
+    /// function plays the same role with that handle threaded through.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must represent a valid, open Unix datagram socket that is not
+    /// owned elsewhere.
+    pub unsafe fn from_raw_fd(fd: RawFd, handle: &Handle) -> io::Result<UnixDatagram> {
+        UnixDatagram::from_std(net::UnixDatagram::from_raw_fd(fd), handle)
+    }
+
     /// Connects the socket to the specified address.
     ///
     /// The `send` method may be used to send data to the specified address.
@@ -398,6 +770,46 @@ impl UnixDatagram {
         return r
     }
 
+    /// Tries to receive data from the socket, without waiting for it to
+    /// become readable.
+    ///
+    /// Unlike `recv`, this does not consult the reactor's readiness state or
+    /// re-register the current task, so the `WouldBlock` error from the
+    /// underlying socket is returned directly to the caller. This is useful
+    /// for opportunistically draining a socket.
+    pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.get_ref().recv(buf)
+    }
+
+    /// Tries to receive data from the socket, without waiting for it to
+    /// become readable.
+    ///
+    /// On success, returns the number of bytes read and the address from
+    /// whence the data came. See `try_recv` for details on why this differs
+    /// from `recv_from`.
+    pub fn try_recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.io.get_ref().recv_from(buf)
+    }
+
+    /// Tries to send data on the socket to the socket's peer, without
+    /// waiting for the socket to become writable.
+    ///
+    /// The peer address may be set by the `connect` method. See `try_recv`
+    /// for details on why this differs from `send`.
+    pub fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.io.get_ref().send(buf)
+    }
+
+    /// Tries to send data on the socket to the specified address, without
+    /// waiting for the socket to become writable.
+    ///
+    /// See `try_recv` for details on why this differs from `send_to`.
+    pub fn try_send_to<P>(&self, buf: &[u8], path: P) -> io::Result<usize>
+        where P: AsRef<Path>
+    {
+        self.io.get_ref().send_to(buf, path)
+    }
+
     /// Returns the value of the `SO_ERROR` option.
     pub fn take_error(&self) -> io::Result<Option<io::Error>> {
         self.io.get_ref().take_error()
@@ -411,6 +823,105 @@ impl UnixDatagram {
     pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
         self.io.get_ref().shutdown(how)
     }
+
+    /// Splits a `UnixDatagram` into a receive half and a send half, which
+    /// can be used to receive and send datagrams concurrently from
+    /// different tasks.
+    pub fn split(self) -> (RecvHalf, SendHalf) {
+        let inner = Arc::new(self);
+        (RecvHalf { inner: inner.clone() }, SendHalf { inner: inner })
+    }
+}
+
+/// The receive half of a `UnixDatagram`, created by `UnixDatagram::split`.
+#[derive(Debug)]
+pub struct RecvHalf {
+    inner: Arc<UnixDatagram>,
+}
+
+/// The send half of a `UnixDatagram`, created by `UnixDatagram::split`.
+#[derive(Debug)]
+pub struct SendHalf {
+    inner: Arc<UnixDatagram>,
+}
+
+/// Error indicating that a `RecvHalf` and `SendHalf` did not originate from
+/// the same `UnixDatagram`, returned by `SendHalf::reunite`.
+#[derive(Debug)]
+pub struct DatagramReuniteError(pub RecvHalf, pub SendHalf);
+
+impl fmt::Display for DatagramReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tried to reunite halves that are not from the same socket")
+    }
+}
+
+impl error::Error for DatagramReuniteError {
+    fn description(&self) -> &str {
+        "tried to reunite halves that are not from the same socket"
+    }
+}
+
+fn reunite_datagram(recv: RecvHalf, send: SendHalf) -> Result<UnixDatagram, DatagramReuniteError> {
+    if Arc::ptr_eq(&recv.inner, &send.inner) {
+        drop(send);
+        Ok(Arc::try_unwrap(recv.inner)
+            .expect("`UnixDatagram` is shared between more than just its two halves"))
+    } else {
+        Err(DatagramReuniteError(recv, send))
+    }
+}
+
+impl RecvHalf {
+    /// Receives data from the socket.
+    ///
+    /// On success, returns the number of bytes read.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf)
+    }
+
+    /// Receives data from the socket.
+    ///
+    /// On success, returns the number of bytes read and the address from
+    /// whence the data came.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf)
+    }
+}
+
+impl SendHalf {
+    /// Sends data on the socket to the socket's peer.
+    ///
+    /// The peer address may be set by the `connect` method, and this method
+    /// will return an error if the socket has not already been connected.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf)
+    }
+
+    /// Sends data on the socket to the specified address.
+    ///
+    /// On success, returns the number of bytes written.
+    pub fn send_to<P: AsRef<Path>>(&self, buf: &[u8], path: P) -> io::Result<usize> {
+        self.inner.send_to(buf, path)
+    }
+
+    /// Connects the socket to the specified address.
+    ///
+    /// The `send` method may be used to send data to the specified address.
+    pub fn connect<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.inner.connect(path)
+    }
+
+    /// Attempts to join the two halves of a `UnixDatagram` back into a
+    /// single datagram socket.
+    ///
+    /// Succeeds only if both halves originated from the same call to
+    /// `UnixDatagram::split`.
+    pub fn reunite(self, other: RecvHalf) -> Result<UnixDatagram, DatagramReuniteError> {
+        reunite_datagram(other, self)
+    }
 }
 
 impl fmt::Debug for UnixDatagram {